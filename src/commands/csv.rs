@@ -14,27 +14,44 @@ use std::{
     io::prelude::*,
 };
 
-extern crate map_in_place;
-
-use map_in_place::MapVecInPlace;
 use crate::printer::Printer;
 use crate::data::{ColumnType, BinaryReader};
 use crate::errors::JobResult;
 use crate::stream::ValueReceiver;
 
+/// Number of data rows sampled to infer column types when `header` is set.
+const HEADER_SAMPLE_SIZE: usize = 100;
+
 pub struct Config {
     separator: char,
+    quote: char,
     columns: Vec<ColumnType>,
-    skip_head: usize,
     trim: Option<char>,
-    input: Box<dyn BinaryReader>,
+    buffered: Vec<Record>,
+    reader: BufReader<Box<dyn Read>>,
+    line_no: usize,
+}
+
+/// One logical CSV row together with the source location it was read from,
+/// so that a malformed row can be reported with the line it came from
+/// instead of as an undifferentiated error.
+struct Record {
+    fields: Vec<String>,
+    /// The line the row started on (1-based). A row spanning several
+    /// physical lines because of an embedded newline is reported at its
+    /// first line.
+    line: usize,
+    /// The raw, unparsed text of the row, for echoing back to the user.
+    raw: String,
 }
 
 fn parse(arguments: Vec<Argument>, input: ValueReceiver) -> JobResult<Config> {
     let mut separator = ',';
+    let mut quote = '"';
     let mut columns = Vec::new();
     let mut skip_head = 0;
     let mut trim = None;
+    let mut header = false;
     let mut files = Vec::new();
 
     for arg in arguments {
@@ -54,6 +71,8 @@ fn parse(arguments: Vec<Argument>, input: ValueReceiver) -> JobResult<Config> {
 
                     ("head", Value::Integer(s)) => skip_head = s as usize,
 
+                    ("header", Value::Bool(b)) => header = b,
+
                     ("sep", Value::Text(s)) => {
                         if s.len() == 1 {
                             separator = s.chars().next().unwrap();
@@ -62,6 +81,14 @@ fn parse(arguments: Vec<Argument>, input: ValueReceiver) -> JobResult<Config> {
                         }
                     }
 
+                    ("quote", Value::Text(s)) => {
+                        if s.len() == 1 {
+                            quote = s.chars().next().unwrap();
+                        } else {
+                            return Err(argument_error("Quote must be exactly one character long"));
+                        }
+                    }
+
                     ("trim", Value::Text(s)) => {
                         if s.len() == 1 {
                             trim = Some(s.chars().next().unwrap());
@@ -76,7 +103,7 @@ fn parse(arguments: Vec<Argument>, input: ValueReceiver) -> JobResult<Config> {
         }
     }
 
-    let reader = match files.len() {
+    let input_reader = match files.len() {
             0 => {
                 let v = input.recv()?;
                 match v {
@@ -92,59 +119,247 @@ fn parse(arguments: Vec<Argument>, input: ValueReceiver) -> JobResult<Config> {
             _ => Err(argument_error("Expected a file name"))
         }?;
 
+    if header && !columns.is_empty() {
+        return Err(argument_error("Cannot combine header with explicit col parameters"));
+    }
+
+    let mut reader: BufReader<Box<dyn Read>> = BufReader::new(input_reader.reader()?);
+    let mut line_no = 0usize;
+
+    for _ in 0..skip_head {
+        read_record(&mut reader, separator, quote, trim, &mut line_no).map_err(io_err)?;
+    }
+
+    let mut buffered = Vec::new();
+
+    if header {
+        let names = read_record(&mut reader, separator, quote, trim, &mut line_no)
+            .map_err(io_err)?
+            .ok_or_else(|| argument_error("Expected a header row"))?
+            .fields;
+
+        while buffered.len() < HEADER_SAMPLE_SIZE {
+            match read_record(&mut reader, separator, quote, trim, &mut line_no).map_err(io_err)? {
+                Some(row) => buffered.push(row),
+                None => break,
+            }
+        }
+
+        columns = names.iter()
+            .enumerate()
+            .map(|(idx, name)| {
+                let samples: Vec<&str> = buffered.iter()
+                    .filter(|row| row.fields.len() == names.len())
+                    .map(|row| row.fields[idx].as_str())
+                    .collect();
+                ColumnType::named(name, infer_type(&samples))
+            })
+            .collect();
+    }
+
     Ok(Config {
         separator,
+        quote,
         columns,
-        skip_head,
         trim,
-        input: reader,
+        buffered,
+        reader,
+        line_no,
     })
 }
 
-fn run(cfg: Config, output: OutputStream, printer: Printer) -> JobResult<()> {
-
-    let printer_copy = printer.clone();
+fn io_err(e: std::io::Error) -> JobError {
+    argument_error(format!("csv: {}", e).as_str())
+}
 
-    let separator = cfg.separator.clone();
-    let trim = cfg.trim.clone();
-    let columns = cfg.columns.clone();
-    let skip = cfg.skip_head;
+/// Pick the narrowest `ValueType` that parses every value in `samples`,
+/// trying `Integer`, then `Float`, then `Bool`, and falling back to `Text`.
+fn infer_type(samples: &[&str]) -> ValueType {
+    if samples.is_empty() {
+        return ValueType::Text;
+    }
+    if samples.iter().all(|s| ValueType::Integer.parse(s).is_ok()) {
+        ValueType::Integer
+    } else if samples.iter().all(|s| ValueType::Float.parse(s).is_ok()) {
+        ValueType::Float
+    } else if samples.iter().all(|s| ValueType::Bool.parse(s).is_ok()) {
+        ValueType::Bool
+    } else {
+        ValueType::Text
+    }
+}
 
-    let mut reader = BufReader::new(cfg.input.reader());
+/// Read one logical CSV row from `reader`, honouring RFC 4180 quoting.
+///
+/// A field may be wrapped in `quote`, in which case `separator` and any
+/// newlines inside the quotes are taken literally and a doubled quote
+/// collapses to one. Because a quoted field can span several physical
+/// lines, this keeps reading lines until the quotes are balanced. Returns
+/// `Ok(None)` once there is nothing left to read. `line_no` tracks the
+/// physical line last read, so callers can report the line a bad row
+/// started on.
+fn read_record(
+    reader: &mut impl BufRead,
+    separator: char,
+    quote: char,
+    trim: Option<char>,
+    line_no: &mut usize,
+) -> std::io::Result<Option<Record>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    // Chars trim would strip, buffered here rather than pushed to `field`
+    // until we know whether a quote follows -- a quote only opens a field
+    // when it is the first non-trimmed character.
+    let mut leading_trim = String::new();
+    let mut in_quotes = false;
+    let mut quoted_field = false;
+    let mut field_started = false;
+    let mut any_input = false;
+    let mut row_done = false;
     let mut line = String::new();
-    let mut skipped = 0usize;
+    let mut raw = String::new();
+    let mut start_line = 0usize;
+
     loop {
         line.clear();
-        reader.read_line(&mut line);
-        if line.is_empty() {
+        let bytes_read = reader.read_line(&mut line)?;
+        if bytes_read == 0 {
             break;
         }
-        if skipped < skip {
-            skipped += 1;
-            continue;
+        any_input = true;
+        *line_no += 1;
+        if raw.is_empty() {
+            start_line = *line_no;
         }
-        let line_without_newline = &line[0..line.len() - 1];
-        let mut split: Vec<&str> = line_without_newline
-            .split(separator)
-            .map(|s| trim
-                .map(|c| s.trim_matches(c))
-                .unwrap_or(s))
-            .collect();
-        if split.len() != columns.len() {
-            printer_copy.error("csv: Wrong number of columns in CSV file");
+        raw.push_str(&line);
+
+        let mut chars = line.chars().peekable();
+        while let Some(ch) = chars.next() {
+            if in_quotes {
+                if ch == quote {
+                    if chars.peek() == Some(&quote) {
+                        field.push(quote);
+                        chars.next();
+                    } else {
+                        in_quotes = false;
+                    }
+                } else {
+                    field.push(ch);
+                }
+                continue;
+            }
+
+            if !field_started && ch != quote && trim == Some(ch) {
+                leading_trim.push(ch);
+                continue;
+            }
+
+            if ch == quote && !field_started {
+                in_quotes = true;
+                quoted_field = true;
+                field_started = true;
+                leading_trim.clear();
+            } else if ch == separator {
+                field.push_str(&leading_trim);
+                leading_trim.clear();
+                fields.push(finish_field(field, quoted_field, trim));
+                field = String::new();
+                quoted_field = false;
+                field_started = false;
+            } else if ch == '\r' {
+                // swallowed; a following '\n' ends the row
+            } else if ch == '\n' {
+                row_done = true;
+            } else {
+                field.push_str(&leading_trim);
+                leading_trim.clear();
+                field.push(ch);
+                field_started = true;
+            }
+
+            if row_done {
+                break;
+            }
         }
-        if let Some(trim) = trim {
-            split = split.map(|s| s.trim_matches(trim));
+
+        if row_done || !in_quotes {
+            break;
         }
+    }
+
+    if !any_input {
+        return Ok(None);
+    }
+
+    field.push_str(&leading_trim);
+    fields.push(finish_field(field, quoted_field, trim));
+    Ok(Some(Record { fields, line: start_line, raw }))
+}
 
-        match split.iter()
-            .zip(columns.iter())
-            .map({ |(s, t)| t.cell_type.parse(*s) })
-            .collect::<Result<Vec<Value>, JobError>>() {
-            Ok(cells) => { output.send(Row::new(cells)); }
-            Err(err) => { printer_copy.job_error(err); }
+fn finish_field(field: String, quoted_field: bool, trim: Option<char>) -> String {
+    if quoted_field {
+        field
+    } else {
+        match trim {
+            Some(c) => field.trim_matches(c).to_string(),
+            None => field,
         }
     }
+}
+
+/// Echo the raw row text for a diagnostic, trimming the trailing newline and
+/// collapsing an embedded-newline row onto one display line.
+fn display_raw(raw: &str) -> String {
+    raw.trim_end_matches(['\r', '\n'].as_ref()).replace('\n', "\\n")
+}
+
+fn emit_row(record: Record, columns: &[ColumnType], output: &OutputStream, printer: &Printer) {
+    if record.fields.len() != columns.len() {
+        printer.error(format!(
+            "csv: line {}: expected {} columns, got {}: {}",
+            record.line,
+            columns.len(),
+            record.fields.len(),
+            display_raw(&record.raw),
+        ).as_str());
+        return;
+    }
+
+    match record.fields.iter()
+        .zip(columns.iter())
+        .map({ |(s, t)| t.cell_type.parse(s.as_str())
+            .map_err(|err| argument_error(format!(
+                "csv: line {}: column '{}': {}: {}",
+                record.line, t.name, err, display_raw(&record.raw),
+            ).as_str())) })
+        .collect::<Result<Vec<Value>, JobError>>() {
+        Ok(cells) => { output.send(Row::new(cells)); }
+        Err(err) => { printer.job_error(err); }
+    }
+}
+
+fn run(mut cfg: Config, output: OutputStream, printer: Printer) -> JobResult<()> {
+
+    let printer_copy = printer.clone();
+
+    let separator = cfg.separator.clone();
+    let quote = cfg.quote.clone();
+    let trim = cfg.trim.clone();
+    let columns = cfg.columns.clone();
+    let mut line_no = cfg.line_no;
+
+    for record in cfg.buffered.drain(..) {
+        emit_row(record, &columns, &output, &printer_copy);
+    }
+
+    loop {
+        let record = match read_record(&mut cfg.reader, separator, quote, trim, &mut line_no) {
+            Ok(Some(record)) => record,
+            Ok(None) => break,
+            Err(_) => break,
+        };
+        emit_row(record, &columns, &output, &printer_copy);
+    }
     return Ok(());
 }
 
@@ -154,3 +369,60 @@ pub fn perform(context: CompileContext) -> JobResult<()> {
         cfg.columns.clone())?;
     run(cfg, output, context.printer)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn read_all(input: &str, separator: char, quote: char, trim: Option<char>) -> Vec<Vec<String>> {
+        let mut reader = Cursor::new(input.as_bytes());
+        let mut line_no = 0usize;
+        let mut rows = Vec::new();
+        while let Some(record) = read_record(&mut reader, separator, quote, trim, &mut line_no).unwrap() {
+            rows.push(record.fields);
+        }
+        rows
+    }
+
+    #[test]
+    fn quote_only_opens_a_field_after_leading_trim_chars() {
+        let rows = read_all("  \"a,b\",c\n", ',', '"', Some(' '));
+        assert_eq!(rows, vec![vec!["a,b".to_string(), "c".to_string()]]);
+    }
+
+    #[test]
+    fn unquoted_leading_trim_chars_are_still_stripped() {
+        let rows = read_all("  a , b\n", ',', '"', Some(' '));
+        assert_eq!(rows, vec![vec!["a".to_string(), "b".to_string()]]);
+    }
+
+    #[test]
+    fn quoted_field_spans_multiple_lines_with_a_doubled_quote_before_the_break() {
+        let rows = read_all("\"He said \"\"hi\"\"\nand left\",done\n", ',', '"', None);
+        assert_eq!(
+            rows,
+            vec![vec!["He said \"hi\"\nand left".to_string(), "done".to_string()]]
+        );
+    }
+
+    #[test]
+    fn infer_type_falls_back_to_text_on_mixed_samples() {
+        assert_eq!(infer_type(&["1", "abc", "2"]), ValueType::Text);
+    }
+
+    #[test]
+    fn infer_type_picks_integer_when_every_sample_parses() {
+        assert_eq!(infer_type(&["1", "2", "3"]), ValueType::Integer);
+    }
+
+    #[test]
+    fn infer_type_picks_float_when_samples_need_a_decimal_point() {
+        assert_eq!(infer_type(&["1", "2.5", "3"]), ValueType::Float);
+    }
+
+    #[test]
+    fn infer_type_of_no_samples_is_text() {
+        assert_eq!(infer_type(&[]), ValueType::Text);
+    }
+}
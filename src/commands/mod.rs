@@ -0,0 +1,88 @@
+use crate::data::Argument;
+use crate::errors::JobResult;
+use crate::printer::Printer;
+use crate::stream::{UninitializedOutputStream, ValueReceiver};
+use lazy_static::lazy_static;
+use ordered_map::OrderedMap;
+
+pub mod csv;
+pub mod to_csv;
+
+/// Everything a command needs to run: the arguments it was called with, the
+/// stream piped into it, a stream it can send its own output to once it
+/// knows the shape of that output, and a printer for side-channel
+/// diagnostics that aren't part of the output stream itself.
+pub struct CompileContext {
+    pub arguments: Vec<Argument>,
+    pub input: ValueReceiver,
+    pub output: UninitializedOutputStream,
+    pub printer: Printer,
+}
+
+pub type CommandFunc = fn(CompileContext) -> JobResult<()>;
+
+/// A single registered command: its entry point, plus the usage string and
+/// description shown wherever commands are listed, the same metadata
+/// `lib/types/glob.rs`'s `METHODS` table carries for each method.
+pub struct Command {
+    pub call: CommandFunc,
+    pub usage: &'static str,
+    pub description: &'static str,
+}
+
+trait CommandBinder {
+    fn declare(&mut self, name: &str, call: CommandFunc, usage: &'static str, description: &'static str);
+}
+
+impl CommandBinder for OrderedMap<String, Command> {
+    fn declare(&mut self, name: &str, call: CommandFunc, usage: &'static str, description: &'static str) {
+        self.insert(name.to_string(), Command { call, usage, description });
+    }
+}
+
+lazy_static! {
+    /// Every command reachable from a crush pipeline, by the name a user
+    /// types, in declaration order.
+    pub static ref COMMANDS: OrderedMap<String, Command> = {
+        let mut res: OrderedMap<String, Command> = OrderedMap::new();
+        res.declare(
+            "csv",
+            csv::perform,
+            "csv [col=name:type]... [sep=c] [quote=c] [trim=c] [head=n] [header=bool] [file]",
+            "Parse a CSV stream or file into rows",
+        );
+        res.declare(
+            "to_csv",
+            to_csv::perform,
+            "to_csv [sep=c] [quote=c] [header=bool]",
+            "Render a stream of rows as CSV",
+        );
+        res
+    };
+}
+
+/// Look up a command by the name a user typed.
+pub fn find(name: &str) -> Option<&'static Command> {
+    COMMANDS.get(&name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_csv_is_registered_and_reachable() {
+        let found = find("to_csv").expect("to_csv should be registered alongside csv");
+        assert_eq!(found.call as usize, to_csv::perform as usize);
+        assert!(!found.usage.is_empty());
+        assert!(!found.description.is_empty());
+    }
+
+    #[test]
+    fn csv_is_registered() {
+        let found = find("csv").expect("csv should be registered");
+        assert_eq!(found.call as usize, csv::perform as usize);
+        assert!(!found.usage.is_empty());
+        assert!(!found.description.is_empty());
+    }
+}
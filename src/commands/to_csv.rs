@@ -0,0 +1,122 @@
+use crate::commands::CompileContext;
+use crate::{
+    data::{
+        Argument,
+        ColumnType,
+        Row,
+        Value,
+    },
+    errors::argument_error,
+};
+use crate::printer::Printer;
+use crate::errors::JobResult;
+use crate::stream::{InputStream, ValueReceiver};
+
+pub struct Config {
+    separator: char,
+    quote: char,
+    header: bool,
+    input: Box<dyn InputStream>,
+}
+
+fn parse(arguments: Vec<Argument>, input: ValueReceiver) -> JobResult<Config> {
+    let mut separator = ',';
+    let mut quote = '"';
+    let mut header = false;
+
+    for arg in arguments {
+        match &arg.name {
+            None => return Err(argument_error("Unknown parameter")),
+            Some(name) => {
+                match (name.as_ref(), arg.value) {
+                    ("sep", Value::Text(s)) => {
+                        if s.len() == 1 {
+                            separator = s.chars().next().unwrap();
+                        } else {
+                            return Err(argument_error("Separator must be exactly one character long"));
+                        }
+                    }
+
+                    ("quote", Value::Text(s)) => {
+                        if s.len() == 1 {
+                            quote = s.chars().next().unwrap();
+                        } else {
+                            return Err(argument_error("Quote must be exactly one character long"));
+                        }
+                    }
+
+                    ("header", Value::Bool(b)) => header = b,
+
+                    _ => return Err(argument_error(format!("Unknown parameter {}", name).as_str())),
+                }
+            }
+        }
+    }
+
+    let stream = match input.recv()? {
+        Value::Stream(s) => s,
+        _ => return Err(argument_error("Expected a stream of rows")),
+    };
+
+    Ok(Config {
+        separator,
+        quote,
+        header,
+        input: stream,
+    })
+}
+
+/// Render a single RFC 4180 field, quoting it if it contains the separator,
+/// the quote character, or a newline, and doubling any quote it contains.
+fn escape_field(value: &str, separator: char, quote: char) -> String {
+    if value.contains(separator) || value.contains(quote) || value.contains('\n') || value.contains('\r') {
+        let mut escaped = String::with_capacity(value.len() + 2);
+        escaped.push(quote);
+        for c in value.chars() {
+            if c == quote {
+                escaped.push(quote);
+            }
+            escaped.push(c);
+        }
+        escaped.push(quote);
+        escaped
+    } else {
+        value.to_string()
+    }
+}
+
+fn render_line(fields: Vec<String>, separator: char) -> String {
+    fields.join(&separator.to_string())
+}
+
+fn render_row(row: &Row, separator: char, quote: char) -> String {
+    let fields = row.cells.iter()
+        .map(|v| escape_field(&v.to_string(), separator, quote))
+        .collect();
+    render_line(fields, separator)
+}
+
+fn run(cfg: Config, printer: Printer) -> JobResult<()> {
+    let columns: Vec<ColumnType> = cfg.input.types().to_vec();
+
+    if cfg.header {
+        let fields = columns.iter()
+            .map(|c| escape_field(&c.name, cfg.separator, cfg.quote))
+            .collect();
+        printer.line(render_line(fields, cfg.separator).as_str());
+    }
+
+    let mut input = cfg.input;
+    loop {
+        match input.recv() {
+            Ok(row) => printer.line(render_row(&row, cfg.separator, cfg.quote).as_str()),
+            Err(_) => break,
+        }
+    }
+    Ok(())
+}
+
+pub fn perform(context: CompileContext) -> JobResult<()> {
+    let cfg = parse(context.arguments, context.input)?;
+    run(cfg, context.printer)
+}
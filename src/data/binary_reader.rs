@@ -0,0 +1,59 @@
+use crate::errors::{JobResult, argument_error};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+extern crate flate2;
+
+use flate2::read::MultiGzDecoder;
+
+/// Magic bytes that open a gzip stream (RFC 1952).
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// A source of bytes for any command that reads a file or a piped-in stream
+/// of raw bytes (`csv`, and anything else built the same way). Gzip
+/// detection lives here so every such command gets it for free, rather than
+/// each command having to wire it in itself. `reader` may be called more
+/// than once; each call yields an independent, freshly-opened stream.
+pub trait BinaryReader {
+    fn reader(&self) -> JobResult<Box<dyn Read>>;
+}
+
+struct FileBinaryReader {
+    path: PathBuf,
+}
+
+impl BinaryReader for FileBinaryReader {
+    fn reader(&self) -> JobResult<Box<dyn Read>> {
+        let raw: Box<dyn Read> = Box::new(
+            File::open(&self.path).map_err(|e| argument_error(format!("{}", e).as_str()))?
+        );
+        gzip_aware(raw)
+    }
+}
+
+impl dyn BinaryReader {
+    /// A reader for `file`. The file is not opened here: each call to
+    /// `reader` opens it and transparently decompresses it if it is gzipped.
+    pub fn from(file: &Path) -> JobResult<Box<dyn BinaryReader>> {
+        Ok(Box::new(FileBinaryReader { path: file.to_path_buf() }))
+    }
+}
+
+/// Wrap `raw` so that, if its content starts with the gzip magic bytes, it
+/// is transparently decompressed. Detection is by content, not by file
+/// extension, so it also works on piped binary input. A `MultiGzDecoder`
+/// is used so concatenated gzip members (as produced by log rotation)
+/// decode as a single stream.
+pub fn gzip_aware(raw: Box<dyn Read>) -> JobResult<Box<dyn Read>> {
+    let mut peekable = BufReader::new(raw);
+    let is_gzip = peekable.fill_buf()
+        .map_err(|e| argument_error(format!("{}", e).as_str()))?
+        .starts_with(&GZIP_MAGIC);
+
+    Ok(if is_gzip {
+        Box::new(MultiGzDecoder::new(peekable))
+    } else {
+        Box::new(peekable)
+    })
+}
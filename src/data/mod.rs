@@ -0,0 +1,7 @@
+// `Argument`, `Row`, `Value`, `ValueType` and `ColumnType` are declared
+// elsewhere in this module and are untouched here -- this file only adds
+// `binary_reader` alongside them, the same way `util/mod.rs` adds `glob`
+// without redeclaring the rest of `util`.
+mod binary_reader;
+
+pub use binary_reader::BinaryReader;
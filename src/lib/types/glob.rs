@@ -10,6 +10,7 @@ use crate::util::file::cwd;
 use crate::util::glob::Glob;
 use lazy_static::lazy_static;
 use ordered_map::OrderedMap;
+use std::path::PathBuf;
 
 fn full(name: &'static str) -> Vec<&'static str> {
     vec!["global", "types", "glob", name]
@@ -49,8 +50,8 @@ lazy_static! {
             full("files"),
             r#files,
             false,
-            "glob:files",
-            "Perform file matching of this glob",
+            "glob:files [directory:string]",
+            "Perform file matching of this glob, starting in the current directory or the given directory",
             None,
             Known(ValueType::List(Box::from(ValueType::File))),
         );
@@ -75,10 +76,15 @@ fn not_match(mut context: ExecutionContext) -> CrushResult<()> {
     context.output.send(Value::Bool(!g.matches(&needle)))
 }
 
-fn files(context: ExecutionContext) -> CrushResult<()> {
+fn files(mut context: ExecutionContext) -> CrushResult<()> {
     let g = context.this.glob()?;
+    let base = if context.arguments.is_empty() {
+        cwd()?
+    } else {
+        PathBuf::from(context.arguments.string(0)?)
+    };
     let mut files = Vec::new();
-    g.glob_files(&cwd()?, &mut files)?;
+    g.glob_files(&base, &mut files)?;
     context.output.send(Value::List(List::new(
         ValueType::File,
         files.drain(..).map(|f| Value::File(f)).collect(),
@@ -0,0 +1,351 @@
+use crate::lang::errors::{CrushResult, to_crush_error};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A single path segment of a compiled pattern, or the recursive `**` marker.
+#[derive(Clone)]
+enum Segment {
+    /// Matches zero or more path segments, crossing directory separators.
+    DoubleStar,
+    /// Matches exactly one path segment against the given tokens.
+    Literal(Vec<SegToken>),
+}
+
+#[derive(Clone)]
+enum SegToken {
+    Char(char),
+    /// `?`, matches exactly one character.
+    Any,
+    /// `*`, matches zero or more characters within a single segment.
+    AnySequence,
+}
+
+/// A glob pattern, such as `src/**/*.rs` or `{foo,bar}.{rs,toml}`.
+///
+/// Brace groups are expanded up front into a set of concrete sub-patterns;
+/// matching or listing files succeeds if any of them succeeds.
+#[derive(Clone)]
+pub struct Glob {
+    original: String,
+    alternatives: Vec<Vec<Segment>>,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Glob {
+        let alternatives = expand_braces(pattern)
+            .iter()
+            .map(|p| compile(p))
+            .collect();
+        Glob { original: pattern.to_string(), alternatives }
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        let parts: Vec<&str> = text.split('/').collect();
+        self.alternatives.iter().any(|segments| matches_segments(segments, &parts))
+    }
+
+    /// List every file under `base` that matches this glob, as paths
+    /// joined onto `base`. Only the subdirectories a pattern could
+    /// actually match are traversed -- a pattern with no `**` never
+    /// recurses below its own depth. Results from every brace alternative
+    /// are unioned, without duplicates.
+    pub fn glob_files(&self, base: &Path, files: &mut Vec<PathBuf>) -> CrushResult<()> {
+        let mut seen = HashSet::new();
+        for segments in &self.alternatives {
+            walk(base, Path::new(""), segments, &mut seen, files)?;
+        }
+        Ok(())
+    }
+}
+
+impl ToString for Glob {
+    fn to_string(&self) -> String {
+        self.original.clone()
+    }
+}
+
+/// Walk `base.join(rel)`, descending only into the subdirectories the
+/// remaining `segments` could still match, and emit `base.join(rel/match)`
+/// for every entry that satisfies them in full.
+fn walk(
+    base: &Path,
+    rel: &Path,
+    segments: &[Segment],
+    seen: &mut HashSet<PathBuf>,
+    out: &mut Vec<PathBuf>,
+) -> CrushResult<()> {
+    match segments.split_first() {
+        None => {
+            if seen.insert(rel.to_path_buf()) {
+                out.push(base.join(rel));
+            }
+            Ok(())
+        }
+        Some((Segment::DoubleStar, rest)) => {
+            // `**` may match zero path segments...
+            walk(base, rel, rest, seen, out)?;
+            // ...or descend through one or more directories.
+            for (name, is_dir) in read_dir_entries(base, rel)? {
+                if is_dir {
+                    walk(base, &rel.join(&name), segments, seen, out)?;
+                }
+            }
+            Ok(())
+        }
+        Some((Segment::Literal(tokens), rest)) => {
+            for (name, is_dir) in read_dir_entries(base, rel)? {
+                if !matches_tokens(tokens, &name) {
+                    continue;
+                }
+                let child = rel.join(&name);
+                if rest.is_empty() {
+                    if seen.insert(child.clone()) {
+                        out.push(base.join(&child));
+                    }
+                } else if is_dir {
+                    walk(base, &child, rest, seen, out)?;
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+fn read_dir_entries(base: &Path, rel: &Path) -> CrushResult<Vec<(String, bool)>> {
+    let mut result = Vec::new();
+    for entry in to_crush_error(fs::read_dir(base.join(rel)))? {
+        let entry = to_crush_error(entry)?;
+        let is_dir = to_crush_error(entry.file_type())?.is_dir();
+        result.push((entry.file_name().to_string_lossy().into_owned(), is_dir));
+    }
+    Ok(result)
+}
+
+fn compile(pattern: &str) -> Vec<Segment> {
+    pattern
+        .split('/')
+        .map(|part| {
+            if part == "**" {
+                Segment::DoubleStar
+            } else {
+                Segment::Literal(compile_segment(part))
+            }
+        })
+        .collect()
+}
+
+fn compile_segment(part: &str) -> Vec<SegToken> {
+    part.chars()
+        .map(|c| match c {
+            '*' => SegToken::AnySequence,
+            '?' => SegToken::Any,
+            c => SegToken::Char(c),
+        })
+        .collect()
+}
+
+fn matches_segments(segments: &[Segment], parts: &[&str]) -> bool {
+    match segments.split_first() {
+        None => parts.is_empty(),
+        Some((Segment::DoubleStar, rest)) => {
+            if matches_segments(rest, parts) {
+                return true;
+            }
+            match parts.split_first() {
+                Some((_, tail)) => matches_segments(segments, tail),
+                None => false,
+            }
+        }
+        Some((Segment::Literal(tokens), rest)) => match parts.split_first() {
+            Some((part, tail)) => matches_tokens(tokens, part) && matches_segments(rest, tail),
+            None => false,
+        },
+    }
+}
+
+fn matches_tokens(tokens: &[SegToken], text: &str) -> bool {
+    let chars: Vec<char> = text.chars().collect();
+    matches_chars(tokens, &chars)
+}
+
+fn matches_chars(tokens: &[SegToken], chars: &[char]) -> bool {
+    match tokens.split_first() {
+        None => chars.is_empty(),
+        Some((SegToken::AnySequence, rest)) => {
+            if matches_chars(rest, chars) {
+                return true;
+            }
+            match chars.split_first() {
+                Some((_, tail)) => matches_chars(tokens, tail),
+                None => false,
+            }
+        }
+        Some((SegToken::Any, rest)) => match chars.split_first() {
+            Some((_, tail)) => matches_chars(rest, tail),
+            None => false,
+        },
+        Some((SegToken::Char(c), rest)) => match chars.split_first() {
+            Some((ch, tail)) if ch == c => matches_chars(rest, tail),
+            _ => false,
+        },
+    }
+}
+
+/// Expand `{a,b,c}` groups into their concrete alternatives. Groups may
+/// nest, e.g. `{foo,{bar,baz}}.rs`, and a pattern may hold several groups,
+/// e.g. `{foo,bar}.{rs,toml}` expands to four patterns.
+fn expand_braces(pattern: &str) -> Vec<String> {
+    expand_braces_chars(&pattern.chars().collect::<Vec<char>>())
+        .iter()
+        .map(|chars| chars.iter().collect())
+        .collect()
+}
+
+fn expand_braces_chars(chars: &[char]) -> Vec<Vec<char>> {
+    let open = match chars.iter().position(|&c| c == '{') {
+        Some(i) => i,
+        None => return vec![chars.to_vec()],
+    };
+
+    let mut depth = 0;
+    let mut close = None;
+    for (i, &c) in chars.iter().enumerate().skip(open) {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    close = Some(i);
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let close = match close {
+        Some(i) => i,
+        None => return vec![chars.to_vec()],
+    };
+
+    let prefix = &chars[..open];
+    let suffix = &chars[close + 1..];
+    let mut result = Vec::new();
+    for alternative in split_top_level_commas(&chars[open + 1..close]) {
+        let mut combined = Vec::new();
+        combined.extend_from_slice(prefix);
+        combined.extend_from_slice(&alternative);
+        combined.extend_from_slice(suffix);
+        result.extend(expand_braces_chars(&combined));
+    }
+    result
+}
+
+fn split_top_level_commas(chars: &[char]) -> Vec<Vec<char>> {
+    let mut result = Vec::new();
+    let mut current = Vec::new();
+    let mut depth = 0;
+    for &c in chars {
+        match c {
+            '{' => {
+                depth += 1;
+                current.push(c);
+            }
+            '}' => {
+                depth -= 1;
+                current.push(c);
+            }
+            ',' if depth == 0 => {
+                result.push(std::mem::replace(&mut current, Vec::new()));
+            }
+            _ => current.push(c),
+        }
+    }
+    result.push(current);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn matches_nested_brace_expansion() {
+        let g = Glob::new("{foo,{bar,baz}}.rs");
+        assert!(g.matches("foo.rs"));
+        assert!(g.matches("bar.rs"));
+        assert!(g.matches("baz.rs"));
+        assert!(!g.matches("qux.rs"));
+    }
+
+    #[test]
+    fn matches_double_star_across_directories() {
+        let g = Glob::new("src/**/*.rs");
+        assert!(g.matches("src/main.rs"));
+        assert!(g.matches("src/a/b/c.rs"));
+        assert!(!g.matches("src/a/b/c.toml"));
+        assert!(!g.matches("other/main.rs"));
+    }
+
+    /// A throwaway directory tree under the OS temp dir, removed on drop.
+    struct TempTree {
+        root: PathBuf,
+    }
+
+    impl TempTree {
+        fn new(name: &str) -> TempTree {
+            let root = std::env::temp_dir().join(format!("crush_glob_test_{}_{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(&root).unwrap();
+            TempTree { root }
+        }
+
+        fn file(&self, rel: &str) {
+            let path = self.root.join(rel);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"").unwrap();
+        }
+    }
+
+    impl Drop for TempTree {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+
+    #[test]
+    fn glob_files_crosses_multiple_directories_and_joins_base() {
+        let tree = TempTree::new("double_star");
+        tree.file("a.rs");
+        tree.file("sub/b.rs");
+        tree.file("sub/deeper/c.rs");
+        tree.file("sub/deeper/c.toml");
+
+        let g = Glob::new("**/*.rs");
+        let mut files = Vec::new();
+        g.glob_files(&tree.root, &mut files).unwrap();
+
+        let mut found: Vec<String> = files.iter()
+            .map(|p| p.strip_prefix(&tree.root).unwrap().to_string_lossy().replace('\\', "/"))
+            .collect();
+        found.sort();
+
+        assert_eq!(found, vec!["a.rs", "sub/b.rs", "sub/deeper/c.rs"]);
+        assert!(files.iter().all(|p| p.starts_with(&tree.root)));
+    }
+
+    #[test]
+    fn glob_files_does_not_descend_past_a_literal_pattern() {
+        let tree = TempTree::new("literal_depth");
+        tree.file("top.rs");
+        tree.file("sub/nested.rs");
+
+        let g = Glob::new("*.rs");
+        let mut files = Vec::new();
+        g.glob_files(&tree.root, &mut files).unwrap();
+
+        assert_eq!(files, vec![tree.root.join("top.rs")]);
+    }
+}